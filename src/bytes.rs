@@ -0,0 +1,162 @@
+// Copyright 2015 Nicholas Allegra (comex).
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Byte-oriented equivalents of the crate's top-level `&str`/`String` API.
+//!
+//! These versions operate on `&[u8]`/`Vec<u8>` directly, so they round-trip arbitrary shell
+//! arguments (such as `OsStr::as_bytes()` on Unix) without requiring the input to be valid UTF-8.
+//! The `&str` functions in the crate root are thin wrappers around these.
+
+#[cfg(feature = "std")]
+use std::{borrow::Cow, vec::Vec};
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::Cow, vec::Vec};
+
+use super::{Error, QuoteError};
+
+/// An iterator that takes a byte slice and splits it into words using the same syntax as the
+/// POSIX shell. See [`crate::Shlex`] for the `&str` equivalent.
+pub struct Shlex<'a> {
+    inner: super::Shlex<core::iter::Copied<core::slice::Iter<'a, u8>>>
+}
+
+impl<'a> Shlex<'a> {
+    pub fn new(in_bytes: &'a [u8]) -> Self {
+        Shlex { inner: super::Shlex::from(in_bytes.iter().copied()) }
+    }
+
+    /// The number of newlines read so far, plus one. Stays at 1 if built with
+    /// [`crate::Builder::track_line_no`] set to `false`.
+    pub fn line_no(&self) -> usize {
+        self.inner.line_no
+    }
+
+    pub(crate) fn from_inner(
+        inner: super::Shlex<core::iter::Copied<core::slice::Iter<'a, u8>>>
+    ) -> Self {
+        Shlex { inner }
+    }
+}
+
+impl<'a> Iterator for Shlex<'a> {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn next(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        self.inner.next_word()
+    }
+}
+
+/// Convenience function that consumes the whole byte slice at once.
+pub fn split(in_bytes: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    Shlex::new(in_bytes).collect()
+}
+
+/// Given a single word, return a byte string suitable to encode it as a shell argument.
+///
+/// Words that need quoting are wrapped in single quotes, since nothing inside single quotes is
+/// subject to shell expansion; an embedded `'` is encoded using the standard `'\''` idiom (close
+/// quote, escaped quote, reopen quote).
+pub fn quote(in_bytes: &[u8]) -> Cow<[u8]> {
+    if in_bytes.len() == 0 {
+        Cow::Borrowed(&b"\"\""[..])
+    } else if in_bytes.iter().any(|&c| match c as char {
+        '|' | '&' | ';' | '<' | '>' | '(' | ')' | '$' | '`' | '\\' | '"' | '\'' | ' ' | '\t' |
+        '\r' | '\n' | '*' | '?' | '[' | '#' | '~' | '=' | '%' => true,
+        _ => false
+    }) {
+        let mut out: Vec<u8> = Vec::new();
+        out.push(b'\'');
+        for &c in in_bytes {
+            if c == b'\'' {
+                out.extend_from_slice(b"'\\''");
+            } else {
+                out.push(c);
+            }
+        }
+        out.push(b'\'');
+        Cow::Owned(out)
+    } else {
+        Cow::Borrowed(in_bytes)
+    }
+}
+
+/// Convenience function that consumes an iterable of words and turns it into a single byte
+/// string, quoting words when necessary. Consecutive words will be separated by a single space.
+pub fn join<'a, I: IntoIterator<Item = &'a [u8]>>(words: I) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::new();
+    for (i, word) in words.into_iter().enumerate() {
+        if i != 0 { out.push(b' '); }
+        out.extend_from_slice(&quote(word));
+    }
+    out
+}
+
+/// Like [`quote`], but refuses to quote words containing a NUL byte or a control character that
+/// cannot be safely pasted into an interactive shell (e.g. ESC), returning [`QuoteError`] instead.
+pub fn try_quote(in_bytes: &[u8]) -> Result<Cow<[u8]>, QuoteError> {
+    for &c in in_bytes {
+        if c == 0 { return Err(QuoteError::Nul); }
+        if c <= 0x1f || c == 0x7f { return Err(QuoteError::ControlCharacter(c)); }
+    }
+    Ok(quote(in_bytes))
+}
+
+/// Like [`join`], but fails if any word cannot be safely quoted; see [`try_quote`].
+pub fn try_join<'a, I: IntoIterator<Item = &'a [u8]>>(words: I) -> Result<Vec<u8>, QuoteError> {
+    let mut out: Vec<u8> = Vec::new();
+    for (i, word) in words.into_iter().enumerate() {
+        if i != 0 { out.push(b' '); }
+        out.extend_from_slice(&try_quote(word)?);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+static SPLIT_TEST_ITEMS: &'static [(&'static [u8], Result<&'static [&'static [u8]], Error>)] = &[
+    (b"foo$baz", Ok(&[b"foo$baz"])),
+    (b"foo baz", Ok(&[b"foo", b"baz"])),
+    (b"   foo \nbar", Ok(&[b"foo", b"bar"])),
+];
+
+#[test]
+fn test_split() {
+    for &(input, output) in SPLIT_TEST_ITEMS {
+        assert_eq!(split(input), output.map(|o| o.iter().map(|&x| x.to_owned()).collect()));
+    }
+}
+
+#[test]
+fn test_quote() {
+    assert_eq!(quote(b"foobar"), &b"foobar"[..]);
+    assert_eq!(quote(b"foo bar"), &b"'foo bar'"[..]);
+    assert_eq!(quote(b"a\x80b c"), &b"'a\x80b c'"[..]);
+    assert_eq!(quote(b"it's"), &b"'it'\\''s'"[..]);
+}
+
+#[test]
+fn test_join() {
+    assert_eq!(join(vec![b"a".as_ref(), b"b".as_ref()]), b"a b");
+}
+
+#[test]
+fn test_try_quote() {
+    assert_eq!(try_quote(b"foobar"), Ok(Cow::Borrowed(&b"foobar"[..])));
+    assert_eq!(try_quote(b"foo\nbar"), Err(QuoteError::ControlCharacter(b'\n')));
+    assert_eq!(try_quote(b"foo\x00bar"), Err(QuoteError::Nul));
+    assert_eq!(try_quote(b"foo\x1bbar"), Err(QuoteError::ControlCharacter(0x1b)));
+}
+
+#[test]
+fn test_try_join() {
+    assert_eq!(try_join(vec![b"a".as_ref(), b"b".as_ref()]), Ok(b"a b".to_vec()));
+    assert_eq!(try_join(vec![b"a\n".as_ref()]), Err(QuoteError::ControlCharacter(b'\n')));
+}
+
+#[test]
+fn test_builder_comments() {
+    let without_comments: Result<Vec<Vec<u8>>, Error> =
+        super::Builder::new().comments(false).build_bytes(b"foo #bar").collect();
+    assert_eq!(without_comments, Ok(vec![b"foo".to_vec(), b"#bar".to_vec()]));
+}
@@ -3,19 +3,36 @@
 // the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
 //! Same idea as (but implementation not directly based on) the Python shlex module.  However, this
-//! implementation does not support any of the Python module's customization because it makes
-//! parsing slower and is fairly useless.  You only get the default settings of shlex.split, which
-//! mimic the POSIX shell:
+//! implementation does not support most of the Python module's customization because it makes
+//! parsing slower and is fairly useless.  By default you only get the default settings of
+//! shlex.split, which mimic the POSIX shell:
 //! http://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html
 //!
+//! [`Builder`] is the exception: it exposes the customization that comes up often in practice,
+//! namely whether (and by which byte) a comment begins, plus whether `line_no` is tracked.
+//!
 //! This implementation also deviates from the Python version in not treating \r specially, which I
 //! believe is more compliant.
 //!
 //! The algorithms in this crate are oblivious to UTF-8 high bytes, so they iterate over the bytes
 //! directly as a micro-optimization.
+//!
+//! With the default-on `std` feature disabled, this crate is `no_std` and only depends on `alloc`
+//! (for `Vec`, `String`, and `Cow`), which makes it usable from embedded and WASM targets that
+//! supply a global allocator but not all of `std`.
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
 
-use std::borrow::Cow;
+#[cfg(feature = "std")]
+use std::{borrow::Cow, string::String, vec::Vec};
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::Cow, string::String, vec::Vec};
+
+pub mod bytes;
 
 /// An error that can occur when splitting a string.
 ///
@@ -30,25 +47,130 @@ pub enum Error {
     UnclosedSingleQuote
 }
 
+/// An error that can occur in [`try_quote`]/[`try_join`] (and their [`bytes`] equivalents) when
+/// the input cannot be safely quoted for a shell.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum QuoteError {
+    /// The input contains a NUL byte, which cannot be represented in a shell command line at all.
+    Nul,
+    /// The input contains a control character other than NUL. There is no portable way to quote
+    /// a literal control byte inside POSIX double or single quotes, and pasting one into an
+    /// interactive shell (e.g. an unescaped ESC) can cause it to interpret injected input.
+    ControlCharacter(u8)
+}
+
 /// An iterator that takes an input string and splits it into the words using the same syntax as
 /// the POSIX shell.
 pub struct Shlex<I: Iterator<Item = u8>> {
     in_iter: I,
-    /// The number of newlines read so far, plus one.
-    pub line_no: usize
+    /// The number of newlines read so far, plus one. Only updated if `track_line_no` is set.
+    pub line_no: usize,
+    comment_char: Option<u8>,
+    track_line_no: bool
 }
 
-impl<'a> Shlex<std::str::Bytes<'a>> {
+impl<'a> Shlex<core::str::Bytes<'a>> {
     pub fn new(in_str: &'a str) -> Self {
         Shlex {
             in_iter: in_str.bytes(),
-            line_no: 1
+            line_no: 1,
+            comment_char: Some(b'#'),
+            track_line_no: true
         }
     }
 }
 
+/// A configurable builder for [`Shlex`] and [`bytes::Shlex`], for callers who need something
+/// other than the default behavior (e.g. disabling `#` comments because `#` is legitimate data
+/// in their input, using a different comment-start byte, or skipping `line_no` bookkeeping).
+///
+/// Settings default to exactly today's `Shlex::new` behavior, so switching an existing caller
+/// over to `Builder::new().build(...)` is a no-op until a setting is changed.
+#[derive(Debug, Clone, Copy)]
+pub struct Builder {
+    comment_char: u8,
+    comments_enabled: bool,
+    track_line_no: bool
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Builder { comment_char: b'#', comments_enabled: true, track_line_no: true }
+    }
+
+    /// Whether a comment-start byte begins a comment that runs to the end of the line. Defaults
+    /// to `true`. Disabling this does not forget a byte set via [`Builder::comment_char`]; it is
+    /// restored if comments are re-enabled.
+    pub fn comments(mut self, comments: bool) -> Self {
+        self.comments_enabled = comments;
+        self
+    }
+
+    /// The byte that begins a comment, implicitly enabling comments. Defaults to `b'#'`.
+    pub fn comment_char(mut self, comment_char: u8) -> Self {
+        self.comment_char = comment_char;
+        self.comments_enabled = true;
+        self
+    }
+
+    /// Whether to maintain [`Shlex::line_no`] as input is consumed. Defaults to `true`; disable
+    /// this to skip the per-byte bookkeeping when the caller doesn't need line numbers.
+    pub fn track_line_no(mut self, track_line_no: bool) -> Self {
+        self.track_line_no = track_line_no;
+        self
+    }
+
+    pub fn build<I: Iterator<Item = u8>>(self, in_iter: I) -> Shlex<I> {
+        Shlex {
+            in_iter,
+            line_no: 1,
+            comment_char: if self.comments_enabled { Some(self.comment_char) } else { None },
+            track_line_no: self.track_line_no
+        }
+    }
+
+    pub fn build_str<'a>(self, in_str: &'a str) -> Shlex<core::str::Bytes<'a>> {
+        self.build(in_str.bytes())
+    }
+
+    /// Like [`Builder::build_str`], but for the byte-oriented [`bytes::Shlex`].
+    pub fn build_bytes<'a>(self, in_bytes: &'a [u8]) -> bytes::Shlex<'a> {
+        bytes::Shlex::from_inner(self.build(in_bytes.iter().copied()))
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
+
 impl<I: Iterator<Item = u8>> Shlex<I> {
-    fn parse_word(&mut self, mut ch: u8) -> Result<String, Error> {
+    /// Skips whitespace and comments, then parses and returns the next word as raw bytes, or
+    /// `None` at the end of input. Shared by the `&str`-oriented `Iterator` impl below and by
+    /// [`bytes::Shlex`].
+    fn next_word(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        if let Some(mut ch) = self.next_char() {
+            // skip initial whitespace
+            loop {
+                match ch as char {
+                    ' ' | '\t' | '\n' => {},
+                    _ if self.comment_char == Some(ch) => {
+                        while let Some(ch2) = self.next_char() {
+                            if ch2 as char == '\n' { break; }
+                        }
+                    },
+                    _ => { break; }
+                }
+                if let Some(ch2) = self.next_char() { ch = ch2; } else { return None; }
+            }
+            Some(self.parse_word(ch))
+        } else { // no initial character
+            None
+        }
+    }
+
+    fn parse_word(&mut self, mut ch: u8) -> Result<Vec<u8>, Error> {
         let mut result: Vec<u8> = Vec::new();
         loop {
             match ch as char {
@@ -64,7 +186,7 @@ impl<I: Iterator<Item = u8>> Shlex<I> {
             }
             if let Some(ch2) = self.next_char() { ch = ch2; } else { break; }
         }
-        Ok(unsafe { String::from_utf8_unchecked(result) })
+        Ok(result)
     }
 
     fn parse_double(&mut self, result: &mut Vec<u8>) -> Result<(), Error> {
@@ -120,7 +242,7 @@ impl<I: Iterator<Item = u8>> Shlex<I> {
 
     fn next_char(&mut self) -> Option<u8> {
         let res = self.in_iter.next();
-        if res == Some('\n' as u8) { self.line_no += 1; }
+        if self.track_line_no && res == Some('\n' as u8) { self.line_no += 1; }
         res
     }
 }
@@ -129,7 +251,9 @@ impl<I: Iterator<Item = u8>, T: IntoIterator<IntoIter = I, Item = u8>> From<T> f
     fn from(into_iter: T) -> Self {
         Shlex {
             in_iter: into_iter.into_iter(),
-            line_no: 1
+            line_no: 1,
+            comment_char: Some(b'#'),
+            track_line_no: true
         }
     }
 }
@@ -138,66 +262,54 @@ impl<I: Iterator<Item = u8>> Iterator for Shlex<I> {
     type Item = Result<String, Error>;
 
     fn next(&mut self) -> Option<Result<String, Error>> {
-        if let Some(mut ch) = self.next_char() {
-            // skip initial whitespace
-            loop {
-                match ch as char {
-                    ' ' | '\t' | '\n' => {},
-                    '#' => {
-                        while let Some(ch2) = self.next_char() {
-                            if ch2 as char == '\n' { break; }
-                        }
-                    },
-                    _ => { break; }
-                }
-                if let Some(ch2) = self.next_char() { ch = ch2; } else { return None; }
-            }
-            Some(self.parse_word(ch))
-        } else { // no initial character
-            None
-        }
+        // `parse_word`/`next_word` only drop or copy bytes, so `result` is valid UTF-8 when the
+        // input iterator yields the bytes of a `&str` (as from `Shlex::new` or
+        // `Builder::build_str`); other byte input is not checked here.
+        self.next_word().map(|r| r.map(|v| unsafe { String::from_utf8_unchecked(v) }))
     }
 
 }
 
-/// Convenience function that consumes the whole string at once.
+/// Convenience function that consumes the whole string at once. Thin wrapper around
+/// [`bytes::split`].
 pub fn split(in_str: &str) -> Result<Vec<String>, Error> {
-    let mut shl = Shlex::new(in_str);
-    shl.by_ref().collect()
+    bytes::split(in_str.as_bytes()).map(|words| words.into_iter()
+        .map(|w| unsafe { String::from_utf8_unchecked(w) })
+        .collect())
 }
 
-/// Given a single word, return a string suitable to encode it as a shell argument.
+/// Given a single word, return a string suitable to encode it as a shell argument. Thin wrapper
+/// around [`bytes::quote`].
 pub fn quote(in_str: &str) -> Cow<str> {
-    if in_str.len() == 0 {
-        "\"\"".into()
-    } else if in_str.bytes().any(|c| match c as char {
-        '|' | '&' | ';' | '<' | '>' | '(' | ')' | '$' | '`' | '\\' | '"' | '\'' | ' ' | '\t' |
-        '\r' | '\n' | '*' | '?' | '[' | '#' | '~' | '=' | '%' => true,
-        _ => false
-    }) {
-        let mut out: Vec<u8> = Vec::new();
-        out.push('"' as u8);
-        for c in in_str.bytes() {
-            match c as char {
-                '$' | '`' | '"' | '\\' => out.push('\\' as u8),
-                _ => ()
-            }
-            out.push(c);
-        }
-        out.push('"' as u8);
-        unsafe { String::from_utf8_unchecked(out) }.into()
-    } else {
-        in_str.into()
+    match bytes::quote(in_str.as_bytes()) {
+        Cow::Borrowed(bytes) => unsafe { core::str::from_utf8_unchecked(bytes) }.into(),
+        Cow::Owned(bytes) => unsafe { String::from_utf8_unchecked(bytes) }.into(),
     }
 }
 
 /// Convenience function that consumes an iterable of words and turns it into a single string,
-/// quoting words when necessary. Consecutive words will be separated by a single space.
+/// quoting words when necessary. Consecutive words will be separated by a single space. Thin
+/// wrapper around [`bytes::join`].
 pub fn join<'a, I: IntoIterator<Item = &'a str>>(words: I) -> String {
-    words.into_iter()
-        .map(quote)
-        .collect::<Vec<_>>()
-        .join(" ")
+    unsafe { String::from_utf8_unchecked(bytes::join(words.into_iter().map(str::as_bytes))) }
+}
+
+/// Given a single word, return a string suitable to encode it as a shell argument, refusing to
+/// quote words containing control characters that cannot be safely pasted into an interactive
+/// shell. Thin wrapper around [`bytes::try_quote`].
+pub fn try_quote(in_str: &str) -> Result<Cow<str>, QuoteError> {
+    match bytes::try_quote(in_str.as_bytes())? {
+        Cow::Borrowed(bytes) => Ok(unsafe { core::str::from_utf8_unchecked(bytes) }.into()),
+        Cow::Owned(bytes) => Ok(unsafe { String::from_utf8_unchecked(bytes) }.into()),
+    }
+}
+
+/// Convenience function that consumes an iterable of words and turns it into a single string,
+/// quoting words when necessary, and failing if any word cannot be safely quoted. Thin wrapper
+/// around [`bytes::try_join`].
+pub fn try_join<'a, I: IntoIterator<Item = &'a str>>(words: I) -> Result<String, QuoteError> {
+    bytes::try_join(words.into_iter().map(str::as_bytes))
+        .map(|bytes| unsafe { String::from_utf8_unchecked(bytes) })
 }
 
 #[cfg(test)]
@@ -240,12 +352,52 @@ fn test_lineno() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_builder_comments() -> Result<(), Error> {
+    let with_comments: Result<Vec<String>, Error> = Builder::new().build_str("foo #bar").collect();
+    assert_eq!(with_comments?, vec!["foo"]);
+    let without_comments: Result<Vec<String>, Error> = Builder::new().comments(false).build_str("foo #bar").collect();
+    assert_eq!(without_comments?, vec!["foo", "#bar"]);
+    Ok(())
+}
+
+#[test]
+fn test_builder_comment_char() -> Result<(), Error> {
+    let words: Result<Vec<String>, Error> = Builder::new().comment_char(b';').build_str("foo ;bar\nbaz").collect();
+    assert_eq!(words?, vec!["foo", "baz"]);
+    Ok(())
+}
+
+#[test]
+fn test_builder_comments_disable_reenable_keeps_comment_char() -> Result<(), Error> {
+    let words: Result<Vec<String>, Error> = Builder::new()
+        .comment_char(b';')
+        .comments(false)
+        .comments(true)
+        .build_str("foo ;bar\nbaz")
+        .collect();
+    assert_eq!(words?, vec!["foo", "baz"]);
+    Ok(())
+}
+
+#[test]
+fn test_builder_track_line_no() -> Result<(), Error> {
+    let mut sh = Builder::new().track_line_no(false).build_str("\nfoo\nbar");
+    while let Some(word) = sh.next() {
+        if word? == "bar" {
+            assert_eq!(sh.line_no, 1);
+        }
+    }
+    Ok(())
+}
+
 #[test]
 fn test_quote() {
     assert_eq!(quote("foobar"), "foobar");
-    assert_eq!(quote("foo bar"), "\"foo bar\"");
-    assert_eq!(quote("\""), "\"\\\"\"");
+    assert_eq!(quote("foo bar"), "'foo bar'");
+    assert_eq!(quote("\""), "'\"'");
     assert_eq!(quote(""), "\"\"");
+    assert_eq!(quote("it's"), "'it'\\''s'");
 }
 
 #[test]
@@ -253,5 +405,19 @@ fn test_join() {
     assert_eq!(join(vec![]), "");
     assert_eq!(join(vec![""]), "\"\"");
     assert_eq!(join(vec!["a", "b"]), "a b");
-    assert_eq!(join(vec!["foo bar", "baz"]), "\"foo bar\" baz");
+    assert_eq!(join(vec!["foo bar", "baz"]), "'foo bar' baz");
+}
+
+#[test]
+fn test_try_quote() {
+    assert_eq!(try_quote("foobar"), Ok("foobar".into()));
+    assert_eq!(try_quote("foo bar"), Ok("'foo bar'".into()));
+    assert_eq!(try_quote("foo\nbar"), Err(QuoteError::ControlCharacter(b'\n')));
+    assert_eq!(try_quote("foo\x1bbar"), Err(QuoteError::ControlCharacter(0x1b)));
+}
+
+#[test]
+fn test_try_join() {
+    assert_eq!(try_join(vec!["a", "b"]), Ok("a b".to_owned()));
+    assert_eq!(try_join(vec!["foo\tbar"]), Err(QuoteError::ControlCharacter(b'\t')));
 }